@@ -0,0 +1,216 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use failure::Error;
+use reqwest::header::{HeaderMap, HeaderValue, USER_AGENT};
+
+use crate::{BitcoinRpcClient, CachingClient};
+
+/// Configures a `BitcoinRpcClient` beyond what `BitcoinRpcClient::new` takes,
+/// e.g. TLS settings for nodes behind an HTTPS proxy. Zero leaves
+/// concurrency, rate and batch size unlimited, as with `new`.
+pub struct BitcoinRpcClientBuilder {
+    uri: String,
+    user: Option<String>,
+    pass: Option<String>,
+    max_concurrency: usize,
+    rps: usize,
+    max_batch_size: usize,
+    root_certificates: Vec<Vec<u8>>,
+    accept_invalid_certs: bool,
+    timeout: Option<Duration>,
+    user_agent: String,
+}
+
+impl BitcoinRpcClientBuilder {
+    pub fn new(uri: impl Into<String>) -> BitcoinRpcClientBuilder {
+        BitcoinRpcClientBuilder {
+            uri: uri.into(),
+            user: None,
+            pass: None,
+            max_concurrency: 0,
+            rps: 0,
+            max_batch_size: 0,
+            root_certificates: Vec::new(),
+            accept_invalid_certs: false,
+            timeout: Some(Duration::from_secs(30)),
+            user_agent: concat!("pivx-rpc-rs/", env!("CARGO_PKG_VERSION")).to_string(),
+        }
+    }
+
+    pub fn auth(mut self, user: impl Into<String>, pass: Option<String>) -> Self {
+        self.user = Some(user.into());
+        self.pass = pass;
+        self
+    }
+
+    pub fn max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = max_concurrency;
+        self
+    }
+
+    pub fn rps(mut self, rps: usize) -> Self {
+        self.rps = rps;
+        self
+    }
+
+    pub fn max_batch_size(mut self, max_batch_size: usize) -> Self {
+        self.max_batch_size = max_batch_size;
+        self
+    }
+
+    /// Trusts a PEM encoded CA certificate in addition to the system roots.
+    /// The certificate is parsed by `build`.
+    pub fn add_root_certificate(mut self, pem: &[u8]) -> Self {
+        self.root_certificates.push(pem.to_vec());
+        self
+    }
+
+    /// Skips certificate and hostname verification. Only meant for testing
+    /// against self-signed setups; prefer `add_root_certificate`.
+    pub fn danger_accept_invalid_certs(mut self, accept_invalid_certs: bool) -> Self {
+        self.accept_invalid_certs = accept_invalid_certs;
+        self
+    }
+
+    /// Per-request timeout, 30 seconds by default. `None` waits forever,
+    /// which suits the long-polling `waitfor*` calls.
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sent as the HTTP `User-Agent`, `pivx-rpc-rs/<version>` by default, so
+    /// operators can tell clients apart in the node's logs.
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Memoizes chain-state reads for `ttl`; finish with `build_cached`. See
+    /// `CachingClient`.
+    pub fn with_cache(self, ttl: Duration) -> CachingClientBuilder {
+        CachingClientBuilder {
+            builder: self,
+            ttl,
+            fee_ttl: None,
+        }
+    }
+
+    /// Memoizes `estimatesmartfee` per target for `ttl`, leaving chain-state
+    /// reads uncached unless `with_cache` is added; finish with `build_cached`.
+    pub fn with_fee_cache(self, ttl: Duration) -> CachingClientBuilder {
+        self.with_cache(Duration::ZERO).with_fee_cache(ttl)
+    }
+
+    pub fn build(self) -> Result<Arc<BitcoinRpcClient>, Error> {
+        let mut headers = HeaderMap::new();
+        headers.insert(USER_AGENT, HeaderValue::from_str(&self.user_agent)?);
+        let mut client = reqwest::Client::builder()
+            .default_headers(headers)
+            .danger_accept_invalid_certs(self.accept_invalid_certs)
+            .timeout(self.timeout);
+        for pem in &self.root_certificates {
+            client = client.add_root_certificate(reqwest::Certificate::from_pem(pem)?);
+        }
+        Ok(Arc::new(BitcoinRpcClient {
+            uri: self.uri,
+            user: self.user,
+            pass: self.pass,
+            max_concurrency: self.max_concurrency,
+            rps: self.rps,
+            counter: (Mutex::new(0), Condvar::new()),
+            last_req: Mutex::new(Instant::now()),
+            max_batch_size: self.max_batch_size,
+            client: client.build()?,
+        }))
+    }
+
+}
+
+/// A `BitcoinRpcClientBuilder` with cache settings, returned by its
+/// `with_cache` and `with_fee_cache`. Only builds a `CachingClient`.
+pub struct CachingClientBuilder {
+    builder: BitcoinRpcClientBuilder,
+    ttl: Duration,
+    fee_ttl: Option<Duration>,
+}
+
+impl CachingClientBuilder {
+    pub fn with_cache(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn with_fee_cache(mut self, ttl: Duration) -> Self {
+        self.fee_ttl = Some(ttl);
+        self
+    }
+
+    pub fn build_cached(self) -> Result<CachingClient, Error> {
+        let client = CachingClient::new(self.builder.build()?, self.ttl);
+        Ok(match self.fee_ttl {
+            Some(fee_ttl) => client.with_fee_cache(fee_ttl),
+            None => client,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{MockChain, MockNode};
+
+    const CERT: &[u8] = include_bytes!("../testdata/mock-node.crt");
+    const KEY: &[u8] = include_bytes!("../testdata/mock-node.key");
+
+    fn tls_node() -> MockNode {
+        let mut chain = MockChain::new();
+        chain.push_block("00", vec![]);
+        MockNode::start_tls(chain, native_tls::Identity::from_pkcs8(CERT, KEY).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn https_works_with_a_custom_root() {
+        let node = tls_node();
+        assert!(node.url().starts_with("https://"));
+        let client = BitcoinRpcClientBuilder::new(node.url()).add_root_certificate(CERT).build().unwrap();
+        assert_eq!(client.getblockcount().unwrap(), 0);
+    }
+
+    #[test]
+    fn https_rejects_an_untrusted_certificate() {
+        let node = tls_node();
+        assert!(BitcoinRpcClientBuilder::new(node.url()).build().unwrap().getblockcount().is_err());
+        let client = BitcoinRpcClientBuilder::new(node.url()).danger_accept_invalid_certs(true).build().unwrap();
+        assert_eq!(client.getblockcount().unwrap(), 0);
+    }
+
+    #[test]
+    fn user_agent_is_sent_with_each_request() {
+        let mut chain = MockChain::new();
+        chain.push_block("00", vec![]);
+        let node = MockNode::start(chain).unwrap();
+        BitcoinRpcClientBuilder::new(node.url()).build().unwrap().getblockcount().unwrap();
+        assert_eq!(node.header("User-Agent").unwrap(), concat!("pivx-rpc-rs/", env!("CARGO_PKG_VERSION")));
+        BitcoinRpcClientBuilder::new(node.url()).user_agent("explorer/2.1").build().unwrap().getblockcount().unwrap();
+        assert_eq!(node.header("user-agent").as_deref(), Some("explorer/2.1"));
+    }
+
+    #[test]
+    fn cache_settings_keep_the_client_settings() {
+        let mut chain = MockChain::new();
+        chain.push_block("00", vec![]);
+        let node = MockNode::start(chain).unwrap();
+        let client = BitcoinRpcClientBuilder::new(node.url())
+            .user_agent("poller/1.0")
+            .with_fee_cache(Duration::from_secs(60))
+            .with_cache(Duration::from_secs(60))
+            .build_cached()
+            .unwrap();
+        client.getblockcount().unwrap();
+        client.getblockcount().unwrap();
+        assert_eq!(node.calls("getblockcount"), 1);
+        assert_eq!(node.header("user-agent").as_deref(), Some("poller/1.0"));
+    }
+}
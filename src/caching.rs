@@ -0,0 +1,274 @@
+use crate::{Address, BitcoinRpcClient, BlockChainInfo, SmartFee};
+use failure::Error;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Memoizes `getblockcount`, `getbestblockhash` and `getblockchaininfo` for a
+/// fixed TTL, and `estimatesmartfee` per target once `with_fee_cache` is set.
+/// Mutating calls made through the wrapper drop the cached values. Every
+/// other method is reached through `inner`, which neither caches nor tracks
+/// writes: call `invalidate` after mutating chain or mempool state there.
+pub struct CachingClient {
+    client: Arc<BitcoinRpcClient>,
+    ttl: Duration,
+    blockcount: Cached<i64>,
+    bestblockhash: Cached<String>,
+    blockchaininfo: Cached<BlockChainInfo>,
+    fee_ttl: Option<Duration>,
+    fees: Mutex<HashMap<u32, (Instant, SmartFee)>>,
+}
+
+impl CachingClient {
+    pub fn new(client: Arc<BitcoinRpcClient>, ttl: Duration) -> Self {
+        CachingClient {
+            client,
+            ttl,
+            blockcount: Cached::default(),
+            bestblockhash: Cached::default(),
+            blockchaininfo: Cached::default(),
+            fee_ttl: None,
+            fees: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Also memoizes `estimatesmartfee` for `ttl`, separately for each
+    /// confirmation target.
+    pub fn with_fee_cache(mut self, ttl: Duration) -> Self {
+        self.fee_ttl = Some(ttl);
+        self
+    }
+
+    pub fn getblockcount(&self) -> Result<i64, Error> {
+        self.blockcount.get_or_fetch(self.ttl, || self.client.getblockcount())
+    }
+
+    pub fn getbestblockhash(&self) -> Result<String, Error> {
+        self.bestblockhash.get_or_fetch(self.ttl, || self.client.getbestblockhash())
+    }
+
+    pub fn getblockchaininfo(&self) -> Result<BlockChainInfo, Error> {
+        self.blockchaininfo.get_or_fetch(self.ttl, || self.client.getblockchaininfo())
+    }
+
+    pub fn estimatesmartfee(&self, conf_target: u32) -> Result<SmartFee, Error> {
+        let ttl = match self.fee_ttl {
+            Some(ttl) => ttl,
+            None => return self.client.estimatesmartfee(conf_target),
+        };
+        let mut fees = self.fees.lock().unwrap();
+        if let Some((fetched_at, fee)) = fees.get(&conf_target) {
+            if fetched_at.elapsed() < ttl {
+                return Ok(fee.clone());
+            }
+        }
+        let fee = self.client.estimatesmartfee(conf_target)?;
+        fees.insert(conf_target, (Instant::now(), fee.clone()));
+        Ok(fee)
+    }
+
+    pub fn generate(&self, number: usize, iterations: Option<usize>) -> Result<Vec<String>, Error> {
+        let result = self.client.generate(number, iterations);
+        self.invalidate();
+        result
+    }
+
+    pub fn generatetoaddress(&self, nblocks: u32, address: &str, max_tries: Option<u32>) -> Result<Vec<String>, Error> {
+        let result = self.client.generatetoaddress(nblocks, address, max_tries);
+        self.invalidate();
+        result
+    }
+
+    pub fn sendrawtransaction(&self, transaction: &str, allow_high_fee: Option<bool>) -> Result<String, Error> {
+        let result = self.client.sendrawtransaction(transaction, allow_high_fee);
+        self.invalidate();
+        result
+    }
+
+    pub fn sendrawtransaction_maxfee(&self, transaction: &str, maxfeerate: Option<f64>) -> Result<String, Error> {
+        let result = self.client.sendrawtransaction_maxfee(transaction, maxfeerate);
+        self.invalidate();
+        result
+    }
+
+    pub fn sendtoaddress(
+        &self,
+        address: &Address,
+        amount: f64,
+        comment: Option<&str>,
+        comment_to: Option<&str>,
+        include_fee: Option<bool>,
+    ) -> Result<String, Error> {
+        let result = self.client.sendtoaddress(address, amount, comment, comment_to, include_fee);
+        self.invalidate();
+        result
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn sendtoaddress_ext(
+        &self,
+        address: &Address,
+        amount: f64,
+        comment: Option<&str>,
+        comment_to: Option<&str>,
+        include_fee: Option<bool>,
+        subtract_fee_from_amount: Option<bool>,
+        use_shield: Option<bool>,
+    ) -> Result<String, Error> {
+        let result = self.client.sendtoaddress_ext(address, amount, comment, comment_to, include_fee, subtract_fee_from_amount, use_shield);
+        self.invalidate();
+        result
+    }
+
+    pub fn submitblock(&self, hex_data: &str, params: Option<&str>) -> Result<Option<String>, Error> {
+        let result = self.client.submitblock(hex_data, params);
+        self.invalidate();
+        result
+    }
+
+    /// Drops every cached value so the next read goes to the node.
+    pub fn invalidate(&self) {
+        self.blockcount.clear();
+        self.bestblockhash.clear();
+        self.blockchaininfo.clear();
+        self.fees.lock().unwrap().clear();
+    }
+
+    /// The wrapped client, for uncached calls. Writes made through it are not
+    /// seen by the cache; see `invalidate`.
+    pub fn inner(&self) -> &Arc<BitcoinRpcClient> {
+        &self.client
+    }
+}
+
+struct Cached<T>(Mutex<Option<(Instant, T)>>);
+
+impl<T> Default for Cached<T> {
+    fn default() -> Self {
+        Cached(Mutex::new(None))
+    }
+}
+
+impl<T: Clone> Cached<T> {
+    /// The lock is held while fetching so concurrent misses hit the node once.
+    fn get_or_fetch(&self, ttl: Duration, fetch: impl FnOnce() -> Result<T, Error>) -> Result<T, Error> {
+        let mut slot = self.0.lock().unwrap();
+        if let Some((fetched_at, ref value)) = *slot {
+            if fetched_at.elapsed() < ttl {
+                return Ok(value.clone());
+            }
+        }
+        let value = fetch()?;
+        *slot = Some((Instant::now(), value.clone()));
+        Ok(value)
+    }
+
+    fn clear(&self) {
+        *self.0.lock().unwrap() = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{MockChain, MockNode};
+    use crate::BitcoinRpcClientBuilder;
+
+    fn node() -> MockNode {
+        let mut chain = MockChain::new();
+        chain.push_block("0000000a", vec![]).push_block("0000000b", vec![]);
+        MockNode::start(chain).unwrap()
+    }
+
+    #[test]
+    fn getblockcount_hits_the_node_once_within_ttl() {
+        let node = node();
+        let client = BitcoinRpcClientBuilder::new(node.url())
+            .with_cache(Duration::from_secs(60))
+            .build_cached()
+            .unwrap();
+        assert_eq!(client.getblockcount().unwrap(), 1);
+        node.push_block("0000000c", vec![]);
+        assert_eq!(client.getblockcount().unwrap(), 1);
+        assert_eq!(node.calls("getblockcount"), 1);
+    }
+
+    #[test]
+    fn expired_values_are_fetched_again() {
+        let node = node();
+        let client = node.client().with_cache(Duration::ZERO);
+        client.getbestblockhash().unwrap();
+        node.push_block("0000000c", vec![]);
+        assert_eq!(client.getbestblockhash().unwrap(), "0000000c");
+        assert_eq!(node.calls("getbestblockhash"), 2);
+    }
+
+    #[test]
+    fn mutating_calls_invalidate() {
+        let node = node();
+        node.set_reply("generate", serde_json::json!(["0000000c"]));
+        let client = node.client().with_cache(Duration::from_secs(60));
+        assert_eq!(client.getblockcount().unwrap(), 1);
+        node.push_block("0000000c", vec![]);
+        client.generate(1, None).unwrap();
+        assert_eq!(client.getblockcount().unwrap(), 2);
+        assert_eq!(node.calls("getblockcount"), 2);
+    }
+
+    #[test]
+    fn inner_calls_bypass_the_cache_until_invalidated() {
+        let node = node();
+        node.set_reply("setgenerate", serde_json::Value::Null);
+        let client = node.client().with_cache(Duration::from_secs(60));
+        assert_eq!(client.getblockcount().unwrap(), 1);
+        client.inner().setgenerate(true, Some(1)).unwrap();
+        node.push_block("0000000c", vec![]);
+        assert_eq!(client.inner().getblockcount().unwrap(), 2);
+        assert_eq!(client.getblockcount().unwrap(), 1);
+        client.invalidate();
+        assert_eq!(client.getblockcount().unwrap(), 2);
+    }
+
+    fn smartfee(feerate: f64) -> serde_json::Value {
+        serde_json::json!({ "feerate": feerate, "blocks": 2 })
+    }
+
+    #[test]
+    fn estimatesmartfee_hits_the_node_once_per_target_within_ttl() {
+        let node = node();
+        node.set_handler("estimatesmartfee", |params| {
+            let target = params[0].as_u64().unwrap_or_default();
+            Ok(smartfee(target as f64 / 10_000.0))
+        });
+        let client = BitcoinRpcClientBuilder::new(node.url())
+            .with_fee_cache(Duration::from_secs(60))
+            .build_cached()
+            .unwrap();
+        assert_eq!(client.estimatesmartfee(2).unwrap().feerate, Some(0.0002));
+        assert_eq!(client.estimatesmartfee(2).unwrap().feerate, Some(0.0002));
+        assert_eq!(node.calls("estimatesmartfee"), 1);
+        assert_eq!(client.estimatesmartfee(6).unwrap().feerate, Some(0.0006));
+        assert_eq!(node.calls("estimatesmartfee"), 2);
+    }
+
+    #[test]
+    fn estimatesmartfee_is_not_cached_by_default() {
+        let node = node();
+        node.set_reply("estimatesmartfee", smartfee(0.0001));
+        let client = node.client().with_cache(Duration::from_secs(60));
+        client.estimatesmartfee(2).unwrap();
+        client.estimatesmartfee(2).unwrap();
+        assert_eq!(node.calls("estimatesmartfee"), 2);
+    }
+
+    #[test]
+    fn invalidate_drops_fee_estimates() {
+        let node = node();
+        node.set_reply("estimatesmartfee", smartfee(0.0001));
+        let client = node.client().with_cache(Duration::from_secs(60)).with_fee_cache(Duration::from_secs(60));
+        client.estimatesmartfee(2).unwrap();
+        client.invalidate();
+        client.estimatesmartfee(2).unwrap();
+        assert_eq!(node.calls("estimatesmartfee"), 2);
+    }
+}
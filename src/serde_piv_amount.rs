@@ -0,0 +1,136 @@
+//! Maps a decimal PIV amount, as the node sends it (number or string), to an
+//! `i64` count of satoshis. Use with `#[serde(with = "serde_piv_amount")]`.
+//!
+//! Applied to output values, which are summed and sent back to the node when
+//! building transactions. Balances, fees and fee rates in wallet and chain
+//! summaries are left as PIV `f64`s.
+
+use serde::de::{self, Deserializer, Visitor};
+use serde::Serializer;
+use std::fmt;
+
+pub const COIN: i64 = 100_000_000;
+
+const DECIMALS: usize = 8;
+
+pub fn serialize<S: Serializer>(sats: &i64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(*sats as f64 / COIN as f64)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+    deserializer.deserialize_any(AmountVisitor)
+}
+
+struct AmountVisitor;
+
+impl<'de> Visitor<'de> for AmountVisitor {
+    type Value = i64;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a PIV amount")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<i64, E> {
+        v.checked_mul(COIN).ok_or_else(|| E::custom(format!("amount {} out of range", v)))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<i64, E> {
+        i64::try_from(v)
+            .ok()
+            .and_then(|v| v.checked_mul(COIN))
+            .ok_or_else(|| E::custom(format!("amount {} out of range", v)))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<i64, E> {
+        let sats = (v * COIN as f64).round();
+        if !sats.is_finite() || sats < i64::MIN as f64 || sats > i64::MAX as f64 {
+            return Err(E::custom(format!("amount {} out of range", v)));
+        }
+        Ok(sats as i64)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<i64, E> {
+        parse_decimal(v).ok_or_else(|| E::custom(format!("invalid amount {:?}", v)))
+    }
+}
+
+/// Parses a decimal string exactly, rejecting more than eight decimal places.
+fn parse_decimal(s: &str) -> Option<i64> {
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+    let (whole, frac) = match digits.split_once('.') {
+        Some((whole, frac)) => (whole, frac),
+        None => (digits, ""),
+    };
+    if (whole.is_empty() && frac.is_empty())
+        || frac.len() > DECIMALS
+        || !whole.bytes().chain(frac.bytes()).all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    let whole: i64 = if whole.is_empty() { 0 } else { whole.parse().ok()? };
+    let frac: i64 = format!("{:0<width$}", frac, width = DECIMALS).parse().ok()?;
+    let sats = whole.checked_mul(COIN)?.checked_add(frac)?;
+    Some(if negative { -sats } else { sats })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Value {
+        #[serde(with = "super")]
+        value: i64,
+    }
+
+    fn sats(json: &str) -> Result<i64, serde_json::Error> {
+        serde_json::from_str::<Value>(&format!(r#"{{"value": {}}}"#, json)).map(|v| v.value)
+    }
+
+    #[test]
+    fn parses_decimal_strings_exactly() {
+        assert_eq!(parse_decimal("0"), Some(0));
+        assert_eq!(parse_decimal("0.00000001"), Some(1));
+        assert_eq!(parse_decimal(".5"), Some(50_000_000));
+        assert_eq!(parse_decimal("1."), Some(COIN));
+        assert_eq!(parse_decimal("21000000.12345678"), Some(2_100_000_012_345_678));
+        assert_eq!(parse_decimal("92233720368.54775807"), Some(i64::MAX));
+    }
+
+    #[test]
+    fn parses_negative_deltas() {
+        assert_eq!(parse_decimal("-0.00000001"), Some(-1));
+        assert_eq!(parse_decimal("-12.5"), Some(-1_250_000_000));
+        assert_eq!(sats("-0.1").unwrap(), -10_000_000);
+        assert_eq!(sats("-3").unwrap(), -3 * COIN);
+    }
+
+    #[test]
+    fn rejects_malformed_or_out_of_range_strings() {
+        for s in ["", "-", ".", "1.000000001", "1e8", "+1", "1.2.3", " 1", "--1", "92233720368.54775808"] {
+            assert_eq!(parse_decimal(s), None, "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn deserializes_numbers_and_strings() {
+        assert_eq!(sats("1").unwrap(), COIN);
+        assert_eq!(sats("0.1").unwrap(), 10_000_000);
+        assert_eq!(sats("0.29999999").unwrap(), 29_999_999);
+        assert_eq!(sats(r#""0.00000001""#).unwrap(), 1);
+        assert!(sats("92233720369").is_err());
+        assert!(sats("1e300").is_err());
+        assert!(sats(r#""abc""#).is_err());
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        for value in [0, 1, -1, 10_000 * COIN, 2_100_000_012_345_678] {
+            let json = serde_json::to_string(&Value { value }).unwrap();
+            assert_eq!(serde_json::from_str::<Value>(&json).unwrap(), Value { value });
+        }
+    }
+}
@@ -0,0 +1,288 @@
+//! Decoding of the `extra_payload` carried by PIVX special transactions
+//! (deterministic masternode registration and updates). Hashes are rendered
+//! in RPC display order, keys and scripts as plain hex.
+
+use crate::{decode_hex, encode_hex};
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExtraPayload {
+    ProReg(ProRegPayload),
+    ProUpServ(ProUpServPayload),
+    ProUpReg(ProUpRegPayload),
+    ProUpRev(ProUpRevPayload),
+}
+
+impl ExtraPayload {
+    pub const PROREG: u64 = 1;
+    pub const PROUPSERV: u64 = 2;
+    pub const PROUPREG: u64 = 3;
+    pub const PROUPREV: u64 = 4;
+
+    /// Returns `None` for unknown transaction types and malformed payloads.
+    pub fn decode(tx_type: u64, payload: &[u8]) -> Option<ExtraPayload> {
+        let mut r = Reader(payload);
+        let decoded = match tx_type {
+            Self::PROREG => ExtraPayload::ProReg(ProRegPayload {
+                version: r.u16()?,
+                mn_type: r.u16()?,
+                mode: r.u16()?,
+                collateral_txid: r.hash()?,
+                collateral_index: r.u32()?,
+                service: r.service()?,
+                owner_key_id: r.hex(20)?,
+                operator_pubkey: r.hex(48)?,
+                voting_key_id: r.hex(20)?,
+                payout_script: r.var_hex()?,
+                operator_reward: r.u16()?,
+                operator_payout_script: r.var_hex()?,
+                inputs_hash: r.hash()?,
+                signature: r.var_hex()?,
+            }),
+            Self::PROUPSERV => ExtraPayload::ProUpServ(ProUpServPayload {
+                version: r.u16()?,
+                pro_tx_hash: r.hash()?,
+                service: r.service()?,
+                operator_payout_script: r.var_hex()?,
+                inputs_hash: r.hash()?,
+                signature: r.hex(96)?,
+            }),
+            Self::PROUPREG => ExtraPayload::ProUpReg(ProUpRegPayload {
+                version: r.u16()?,
+                pro_tx_hash: r.hash()?,
+                mode: r.u16()?,
+                operator_pubkey: r.hex(48)?,
+                voting_key_id: r.hex(20)?,
+                payout_script: r.var_hex()?,
+                inputs_hash: r.hash()?,
+                signature: r.var_hex()?,
+            }),
+            Self::PROUPREV => ExtraPayload::ProUpRev(ProUpRevPayload {
+                version: r.u16()?,
+                pro_tx_hash: r.hash()?,
+                reason: r.u16()?,
+                inputs_hash: r.hash()?,
+                signature: r.hex(96)?,
+            }),
+            _ => return None,
+        };
+        if r.0.is_empty() {
+            Some(decoded)
+        } else {
+            None
+        }
+    }
+
+    pub fn decode_hex(tx_type: u64, payload: &str) -> Option<ExtraPayload> {
+        Self::decode(tx_type, &decode_hex(payload)?)
+    }
+}
+
+/// Registers a masternode (ProRegTx).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProRegPayload {
+    pub version: u16,
+    pub mn_type: u16,
+    pub mode: u16,
+    pub collateral_txid: String,
+    pub collateral_index: u32,
+    pub service: SocketAddr,
+    pub owner_key_id: String,
+    pub operator_pubkey: String,
+    pub voting_key_id: String,
+    pub payout_script: String,
+    pub operator_reward: u16,
+    pub operator_payout_script: String,
+    pub inputs_hash: String,
+    pub signature: String,
+}
+
+/// Updates a masternode's service address (ProUpServTx).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProUpServPayload {
+    pub version: u16,
+    pub pro_tx_hash: String,
+    pub service: SocketAddr,
+    pub operator_payout_script: String,
+    pub inputs_hash: String,
+    pub signature: String,
+}
+
+/// Updates a masternode's operator, voting key or payout (ProUpRegTx).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProUpRegPayload {
+    pub version: u16,
+    pub pro_tx_hash: String,
+    pub mode: u16,
+    pub operator_pubkey: String,
+    pub voting_key_id: String,
+    pub payout_script: String,
+    pub inputs_hash: String,
+    pub signature: String,
+}
+
+/// Revokes a masternode's operator (ProUpRevTx).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProUpRevPayload {
+    pub version: u16,
+    pub pro_tx_hash: String,
+    pub reason: u16,
+    pub inputs_hash: String,
+    pub signature: String,
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Some(head)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes(self.take(2)?.try_into().ok()?))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn compact_size(&mut self) -> Option<usize> {
+        let size = match self.take(1)?[0] {
+            0xfd => u64::from(self.u16()?),
+            0xfe => u64::from(self.u32()?),
+            0xff => u64::from_le_bytes(self.take(8)?.try_into().ok()?),
+            n => u64::from(n),
+        };
+        usize::try_from(size).ok()
+    }
+
+    fn hex(&mut self, n: usize) -> Option<String> {
+        Some(encode_hex(self.take(n)?))
+    }
+
+    fn var_hex(&mut self) -> Option<String> {
+        let n = self.compact_size()?;
+        self.hex(n)
+    }
+
+    fn hash(&mut self) -> Option<String> {
+        let mut hash = self.take(32)?.to_vec();
+        hash.reverse();
+        Some(encode_hex(&hash))
+    }
+
+    fn service(&mut self) -> Option<SocketAddr> {
+        let ip: [u8; 16] = self.take(16)?.try_into().ok()?;
+        let port = u16::from_be_bytes(self.take(2)?.try_into().ok()?);
+        let ip = Ipv6Addr::from(ip);
+        let ip = match ip.to_ipv4_mapped() {
+            Some(ip) => IpAddr::V4(ip),
+            None => IpAddr::V6(ip),
+        };
+        Some(SocketAddr::new(ip, port))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn payout_script() -> String {
+        format!("76a914{}88ac", "44".repeat(20))
+    }
+
+    fn proreg_hex() -> String {
+        let collateral: Vec<u8> = (1..=32).collect();
+        [
+            "0100".to_string(),
+            "0000".to_string(),
+            "0000".to_string(),
+            encode_hex(&collateral),
+            "01000000".to_string(),
+            format!("{}ffff01020304", "00".repeat(10)),
+            "c910".to_string(),
+            "11".repeat(20),
+            "22".repeat(48),
+            "33".repeat(20),
+            format!("19{}", payout_script()),
+            "0000".to_string(),
+            "00".to_string(),
+            "55".repeat(32),
+            format!("41{}", "66".repeat(65)),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn decodes_proreg() {
+        let collateral: Vec<u8> = (1..=32).rev().collect();
+        let expected = ProRegPayload {
+            version: 1,
+            mn_type: 0,
+            mode: 0,
+            collateral_txid: encode_hex(&collateral),
+            collateral_index: 1,
+            service: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4)), 51472),
+            owner_key_id: "11".repeat(20),
+            operator_pubkey: "22".repeat(48),
+            voting_key_id: "33".repeat(20),
+            payout_script: payout_script(),
+            operator_reward: 0,
+            operator_payout_script: String::new(),
+            inputs_hash: "55".repeat(32),
+            signature: "66".repeat(65),
+        };
+        assert_eq!(
+            ExtraPayload::decode_hex(ExtraPayload::PROREG, &proreg_hex()),
+            Some(ExtraPayload::ProReg(expected))
+        );
+    }
+
+    #[test]
+    fn decodes_proupserv_with_ipv6_service() {
+        let hex = [
+            "0100".to_string(),
+            "aa".repeat(32),
+            format!("2001{}01", "00".repeat(13)),
+            "c910".to_string(),
+            "00".to_string(),
+            "bb".repeat(32),
+            "cc".repeat(96),
+        ]
+        .concat();
+        let Some(ExtraPayload::ProUpServ(payload)) = ExtraPayload::decode_hex(ExtraPayload::PROUPSERV, &hex) else {
+            panic!("not a ProUpServ payload");
+        };
+        assert_eq!(payload.pro_tx_hash, "aa".repeat(32));
+        assert_eq!(payload.service, "[2001::1]:51472".parse().unwrap());
+        assert_eq!(payload.operator_payout_script, "");
+        assert_eq!(payload.signature, "cc".repeat(96));
+    }
+
+    #[test]
+    fn rejects_malformed_payloads() {
+        let hex = proreg_hex();
+        assert_eq!(ExtraPayload::decode_hex(ExtraPayload::PROREG, &hex[..hex.len() - 2]), None);
+        assert_eq!(ExtraPayload::decode_hex(ExtraPayload::PROREG, &format!("{}00", hex)), None);
+        assert_eq!(ExtraPayload::decode_hex(ExtraPayload::PROREG, &hex[1..]), None);
+        assert_eq!(ExtraPayload::decode_hex(ExtraPayload::PROUPSERV, &hex), None);
+    }
+
+    #[test]
+    fn ignores_unknown_types() {
+        assert_eq!(ExtraPayload::decode_hex(0, &proreg_hex()), None);
+        assert_eq!(ExtraPayload::decode_hex(9, ""), None);
+
+        let mut tx = crate::testutil::transaction(&"ab".repeat(32));
+        assert_eq!(tx.decode_extra_payload(), None);
+        tx.r#type = ExtraPayload::PROREG;
+        tx.extra_payload = Some(proreg_hex());
+        assert!(matches!(tx.decode_extra_payload(), Some(ExtraPayload::ProReg(_))));
+    }
+}
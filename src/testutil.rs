@@ -0,0 +1,441 @@
+//! A mock JSON-RPC node backed by an in-memory chain, for testing code built
+//! on this crate without a running `pivxd`. Enabled by the `testutil` feature.
+//!
+//! The mock answers `getblockcount`, `getbestblockhash`, `getblockhash`,
+//! `getblock`, `getblockheader` and `getrawtransaction` from the chain. Other
+//! methods can be given a canned reply with `set_reply` or `set_error`, or
+//! computed from their params with `set_handler`; anything else fails with a
+//! method-not-found error. `MockNode::start_tls` serves over HTTPS instead.
+//!
+//! ```
+//! use pivx_rpc_rs::testutil::{self, MockChain, MockNode};
+//! use pivx_rpc_rs::Txid;
+//!
+//! let txid: Txid = "ab".repeat(32).parse().unwrap();
+//! let mut chain = MockChain::new();
+//! chain.push_block("0000000a", vec![]);
+//! chain.push_block("0000000b", vec![testutil::transaction(&txid.to_hex())]);
+//!
+//! let node = MockNode::start(chain).unwrap();
+//! let client = node.client();
+//!
+//! assert_eq!(client.getblockcount().unwrap(), 1);
+//! assert_eq!(client.getblockhash(1).unwrap(), "0000000b");
+//! let tx = client.getrawtransaction(&txid, true, None).unwrap();
+//! assert_eq!(tx.blockhash.as_deref(), Some("0000000b"));
+//! assert_eq!(tx.confirmations, Some(1));
+//! assert_eq!(node.calls("getrawtransaction"), 1);
+//! ```
+
+use crate::{BitcoinRpcClient, Block, FullBlock, GetRawTransactionInfo, RpcError, ShieldPoolValue};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+#[derive(Clone, Debug)]
+pub struct MockBlock {
+    pub hash: String,
+    pub transactions: Vec<GetRawTransactionInfo>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MockChain {
+    pub blocks: Vec<MockBlock>,
+    stale: Vec<StaleBlock>,
+}
+
+/// A block disconnected by `reorg`, still known to `getblockheader`.
+#[derive(Clone, Debug)]
+struct StaleBlock {
+    height: usize,
+    previousblockhash: Option<String>,
+    block: MockBlock,
+}
+
+impl MockChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a block at the next height.
+    pub fn push_block(&mut self, hash: &str, transactions: Vec<GetRawTransactionInfo>) -> &mut Self {
+        self.blocks.push(MockBlock {
+            hash: hash.to_string(),
+            transactions,
+        });
+        self
+    }
+
+    /// Disconnects the top `depth` blocks. They stay known as stale blocks,
+    /// so push the replacement branch afterwards to simulate a reorg.
+    pub fn reorg(&mut self, depth: usize) -> &mut Self {
+        let fork = self.blocks.len().saturating_sub(depth);
+        let mut previousblockhash = fork.checked_sub(1).map(|parent| self.blocks[parent].hash.clone());
+        let disconnected: Vec<_> = self.blocks.drain(fork..).collect();
+        for (i, block) in disconnected.into_iter().enumerate() {
+            self.stale.push(StaleBlock {
+                height: fork + i,
+                previousblockhash: previousblockhash.replace(block.hash.clone()),
+                block,
+            });
+        }
+        self
+    }
+
+    /// Height, parent and confirmations of a known block, active or stale.
+    fn locate(&self, hash: &str) -> Option<(usize, Option<String>, i64, &MockBlock)> {
+        if let Some(height) = self.blocks.iter().position(|block| block.hash == hash) {
+            let previous = height.checked_sub(1).map(|parent| self.blocks[parent].hash.clone());
+            let confirmations = (self.blocks.len() - height) as i64;
+            return Some((height, previous, confirmations, &self.blocks[height]));
+        }
+        self.stale
+            .iter()
+            .rev()
+            .find(|stale| stale.block.hash == hash)
+            .map(|stale| (stale.height, stale.previousblockhash.clone(), -1, &stale.block))
+    }
+
+    fn header(&self, hash: &str) -> Option<Block> {
+        let (height, previousblockhash, confirmations, block) = self.locate(hash)?;
+        Some(Block {
+            hash: block.hash.clone(),
+            confirmations,
+            height: height as i64,
+            version: 1,
+            merkleroot: String::new(),
+            time: 1_600_000_000 + 60 * height as i64,
+            mediantime: 1_600_000_000 + 60 * height as i64,
+            nonce: 0,
+            bits: String::new(),
+            difficulty: 1.0,
+            chainwork: String::new(),
+            acc_checkpoint: String::new(),
+            shield_pool_value: ShieldPoolValue {
+                chain_value: 0.0,
+                value_delta: 0.0,
+                chain_value_sat: None,
+            },
+            previousblockhash,
+        })
+    }
+
+    fn full_block(&self, hash: &str) -> Option<FullBlock> {
+        let header = self.header(hash)?;
+        let (height, _, confirmations, block) = self.locate(hash)?;
+        let nextblockhash = match confirmations {
+            -1 => None,
+            _ => self.blocks.get(height + 1).map(|next| next.hash.clone()),
+        };
+        Some(FullBlock {
+            hash: header.hash,
+            confirmations: confirmations as i32,
+            size: 0,
+            height: header.height,
+            version: header.version,
+            merkleroot: header.merkleroot,
+            acc_checkpoint: header.acc_checkpoint,
+            finalsaplingroot: String::new(),
+            tx: block.transactions.iter().map(|tx| tx.txid.clone()).collect(),
+            time: header.time as u32,
+            mediantime: header.mediantime as u32,
+            nonce: header.nonce,
+            bits: header.bits,
+            difficulty: header.difficulty,
+            chainwork: header.chainwork,
+            previousblockhash: header.previousblockhash,
+            nextblockhash,
+            stakemodifier: None,
+            hashproofofstake: None,
+        })
+    }
+
+    fn handle(&self, method: &str, params: &[Value]) -> Result<Value, RpcError> {
+        let block_not_found = || error(RpcError::INVALID_ADDRESS_OR_KEY, "Block not found");
+        let hash = params.first().and_then(Value::as_str).unwrap_or_default();
+        match method {
+            "getblockcount" => Ok((self.blocks.len() as i64 - 1).into()),
+            "getbestblockhash" => self
+                .blocks
+                .last()
+                .map(|block| block.hash.clone().into())
+                .ok_or_else(block_not_found),
+            "getblockhash" => params
+                .first()
+                .and_then(Value::as_i64)
+                .and_then(|height| self.blocks.get(usize::try_from(height).ok()?))
+                .map(|block| block.hash.clone().into())
+                .ok_or_else(|| error(RpcError::INVALID_PARAMETER, "Block height out of range")),
+            "getblock" => self.full_block(hash).map(to_value).ok_or_else(block_not_found)?,
+            "getblockheader" => match params.get(1) {
+                Some(Value::Bool(false)) => self.locate(hash).map(|_| Value::from("00".repeat(112))).ok_or_else(block_not_found),
+                _ => self.header(hash).map(to_value).ok_or_else(block_not_found)?,
+            },
+            "getrawtransaction" => {
+                let txid = params.first().and_then(Value::as_str).unwrap_or_default();
+                let verbose = match params.get(1) {
+                    Some(Value::Bool(verbose)) => *verbose,
+                    Some(Value::Number(verbose)) => verbose.as_i64() != Some(0),
+                    _ => false,
+                };
+                let tip = self.blocks.len() as u64;
+                for (height, block) in self.blocks.iter().enumerate() {
+                    if let Some(tx) = block.transactions.iter().find(|tx| tx.txid == txid) {
+                        if !verbose {
+                            return Ok(tx.hex.clone().into());
+                        }
+                        let mut tx = tx.clone();
+                        tx.blockhash = Some(block.hash.clone());
+                        tx.confirmations = Some(tip - height as u64);
+                        return to_value(tx);
+                    }
+                }
+                Err(error(-5, "No such mempool or blockchain transaction"))
+            }
+            _ => Err(error(RpcError::METHOD_NOT_FOUND, "Method not found")),
+        }
+    }
+}
+
+/// A minimal transparent transaction, enough for `getrawtransaction` lookups.
+pub fn transaction(txid: &str) -> GetRawTransactionInfo {
+    GetRawTransactionInfo {
+        txid: txid.to_string(),
+        version: 1,
+        r#type: 0,
+        size: 0,
+        locktime: 0,
+        vin: Vec::new(),
+        vout: Vec::new(),
+        hex: String::new(),
+        value_balance: None,
+        value_balance_sat: None,
+        vshield_spend: None,
+        vshield_output: None,
+        binding_sig: None,
+        shielded_addresses: None,
+        extra_payload_size: None,
+        extra_payload: None,
+        blockhash: None,
+        confirmations: None,
+        time: None,
+        blocktime: None,
+    }
+}
+
+fn error(code: i64, message: &str) -> RpcError {
+    RpcError {
+        code,
+        message: message.to_string(),
+    }
+}
+
+fn to_value<T: serde::Serialize>(value: T) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| error(RpcError::MISC_ERROR, &e.to_string()))
+}
+
+type Handler = dyn Fn(&[Value]) -> Result<Value, RpcError> + Send + Sync;
+
+/// Everything the serving threads share with the `MockNode` handle.
+#[derive(Default)]
+struct State {
+    chain: Mutex<MockChain>,
+    handlers: Mutex<HashMap<String, Arc<Handler>>>,
+    requests: Mutex<HashMap<String, Vec<Value>>>,
+    headers: Mutex<HashMap<String, String>>,
+    delay: Mutex<Duration>,
+    in_flight: AtomicUsize,
+    peak_in_flight: AtomicUsize,
+    shutdown: AtomicBool,
+}
+
+impl State {
+    fn reply(&self, request: &Value) -> Value {
+        let method = request["method"].as_str().unwrap_or_default();
+        let params = request["params"].as_array().map(Vec::as_slice).unwrap_or_default();
+        self.requests.lock().unwrap().entry(method.to_string()).or_default().push(request["params"].clone());
+        let handler = self.handlers.lock().unwrap().get(method).cloned();
+        let result = match handler {
+            Some(handler) => handler(params),
+            None => self.chain.lock().unwrap().handle(method, params),
+        };
+        let (result, error) = match result {
+            Ok(result) => (result, Value::Null),
+            Err(e) => (Value::Null, serde_json::to_value(e).unwrap_or_default()),
+        };
+        serde_json::json!({ "result": result, "error": error, "id": request["id"] })
+    }
+}
+
+/// Serves a `MockChain` over HTTP on a local port until dropped.
+pub struct MockNode {
+    addr: SocketAddr,
+    tls: bool,
+    state: Arc<State>,
+}
+
+impl MockNode {
+    pub fn start(chain: MockChain) -> io::Result<MockNode> {
+        MockNode::listen(chain, None)
+    }
+
+    /// Like `start`, but serves HTTPS with `identity`, e.g. a self-signed
+    /// certificate the client is told to trust.
+    pub fn start_tls(chain: MockChain, identity: native_tls::Identity) -> io::Result<MockNode> {
+        let acceptor = native_tls::TlsAcceptor::new(identity).map_err(io::Error::other)?;
+        MockNode::listen(chain, Some(acceptor))
+    }
+
+    fn listen(chain: MockChain, acceptor: Option<native_tls::TlsAcceptor>) -> io::Result<MockNode> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let node = MockNode {
+            addr: listener.local_addr()?,
+            tls: acceptor.is_some(),
+            state: Arc::new(State {
+                chain: Mutex::new(chain),
+                ..State::default()
+            }),
+        };
+        let state = node.state.clone();
+        let acceptor = acceptor.map(Arc::new);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if state.shutdown.load(Ordering::SeqCst) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    let state = state.clone();
+                    let acceptor = acceptor.clone();
+                    thread::spawn(move || match acceptor {
+                        Some(acceptor) => match acceptor.accept(stream) {
+                            Ok(stream) => serve(stream, &state),
+                            Err(e) => Err(io::Error::other(e.to_string())),
+                        },
+                        None => serve(stream, &state),
+                    });
+                }
+            }
+        });
+        Ok(node)
+    }
+
+    pub fn url(&self) -> String {
+        let scheme = if self.tls { "https" } else { "http" };
+        format!("{}://{}", scheme, self.addr)
+    }
+
+    pub fn client(&self) -> Arc<BitcoinRpcClient> {
+        BitcoinRpcClient::new(self.url(), None, None, 0, 0, 0)
+    }
+
+    /// Appends a block to the served chain, e.g. to simulate a new tip.
+    pub fn push_block(&self, hash: &str, transactions: Vec<GetRawTransactionInfo>) {
+        self.state.chain.lock().unwrap().push_block(hash, transactions);
+    }
+
+    /// Disconnects the top `depth` blocks of the served chain; see
+    /// `MockChain::reorg`.
+    pub fn reorg(&self, depth: usize) {
+        self.state.chain.lock().unwrap().reorg(depth);
+    }
+
+    /// Answers `method` with `handler(params)` from now on, taking precedence
+    /// over the chain.
+    pub fn set_handler(&self, method: &str, handler: impl Fn(&[Value]) -> Result<Value, RpcError> + Send + Sync + 'static) {
+        self.state.handlers.lock().unwrap().insert(method.to_string(), Arc::new(handler));
+    }
+
+    /// Answers `method` with `result` from now on.
+    pub fn set_reply(&self, method: &str, result: Value) {
+        self.set_handler(method, move |_| Ok(result.clone()));
+    }
+
+    /// Fails `method` with the given `RpcError` from now on.
+    pub fn set_error(&self, method: &str, code: i64, message: &str) {
+        let e = error(code, message);
+        self.set_handler(method, move |_| Err(e.clone()));
+    }
+
+    /// Holds every reply back for `delay`, e.g. to observe concurrency.
+    pub fn set_delay(&self, delay: Duration) {
+        *self.state.delay.lock().unwrap() = delay;
+    }
+
+    /// How many times `method` was requested, counting batched requests.
+    pub fn calls(&self, method: &str) -> usize {
+        self.params(method).len()
+    }
+
+    /// The params of every `method` request so far, oldest first, as sent.
+    pub fn params(&self, method: &str) -> Vec<Value> {
+        self.state.requests.lock().unwrap().get(method).cloned().unwrap_or_default()
+    }
+
+    /// The `name` header of the latest HTTP request, matched case-insensitively.
+    pub fn header(&self, name: &str) -> Option<String> {
+        self.state.headers.lock().unwrap().get(&name.to_ascii_lowercase()).cloned()
+    }
+
+    /// The most HTTP requests that were being answered at the same time.
+    pub fn peak_in_flight(&self) -> usize {
+        self.state.peak_in_flight.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for MockNode {
+    fn drop(&mut self) {
+        self.state.shutdown.store(true, Ordering::SeqCst);
+        // Wake the accept loop so it notices the flag.
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+fn serve<S: Read + Write>(stream: S, state: &State) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let mut headers = HashMap::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            let header = line.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                headers.insert(name.to_ascii_lowercase(), value.trim().to_string());
+            }
+        }
+        let content_length = headers.get("content-length").and_then(|len| len.parse().ok()).unwrap_or(0);
+        *state.headers.lock().unwrap() = headers;
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body)?;
+        let in_flight = state.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        state.peak_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+        thread::sleep(*state.delay.lock().unwrap());
+        let request: Value = serde_json::from_slice(&body).unwrap_or_default();
+        let reply = match request {
+            Value::Array(batch) => Value::Array(batch.iter().map(|r| state.reply(r)).collect()),
+            request => state.reply(&request),
+        }
+        .to_string();
+        state.in_flight.fetch_sub(1, Ordering::SeqCst);
+        let stream = reader.get_mut();
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n",
+            reply.len()
+        )?;
+        stream.write_all(reply.as_bytes())?;
+    }
+}
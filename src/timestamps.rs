@@ -0,0 +1,82 @@
+use chrono::{DateTime, Utc};
+
+use crate::{Block, FullBlock, GetRawTransactionInfo, Transaction};
+
+/// Timestamps outside chrono's range can only come from a corrupt reply and
+/// map to the epoch, like the `0` the node reports for unset times.
+fn utc(secs: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(secs, 0).unwrap_or_default()
+}
+
+impl Block {
+    pub fn time_utc(&self) -> DateTime<Utc> {
+        utc(self.time)
+    }
+
+    pub fn mediantime_utc(&self) -> DateTime<Utc> {
+        utc(self.mediantime)
+    }
+}
+
+impl FullBlock {
+    pub fn time_utc(&self) -> DateTime<Utc> {
+        utc(self.time.into())
+    }
+
+    pub fn mediantime_utc(&self) -> DateTime<Utc> {
+        utc(self.mediantime.into())
+    }
+}
+
+impl Transaction {
+    pub fn time_utc(&self) -> Option<DateTime<Utc>> {
+        self.time.map(|time| utc(time.into()))
+    }
+
+    pub fn blocktime_utc(&self) -> Option<DateTime<Utc>> {
+        self.blocktime.map(|time| utc(time.into()))
+    }
+}
+
+impl GetRawTransactionInfo {
+    pub fn time_utc(&self) -> Option<DateTime<Utc>> {
+        self.time.map(|time| utc(time as i64))
+    }
+
+    pub fn blocktime_utc(&self) -> Option<DateTime<Utc>> {
+        self.blocktime.map(|time| utc(time as i64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{transaction, MockChain, MockNode};
+
+    #[test]
+    fn block_times_convert_to_utc() {
+        let mut chain = MockChain::new();
+        chain.push_block("00", vec![]);
+        let node = MockNode::start(chain).unwrap();
+        let mut block = node.client().getblockheader("00".to_string(), true).unwrap().Verbose().unwrap();
+        block.time = 1_454_124_731;
+        block.mediantime = 1_454_124_600;
+        assert_eq!(block.time_utc().to_rfc3339(), "2016-01-30T03:32:11+00:00");
+        assert_eq!(block.mediantime_utc().timestamp(), 1_454_124_600);
+
+        block.time = 0;
+        assert_eq!(block.time_utc(), DateTime::UNIX_EPOCH);
+        block.time = i64::MAX;
+        assert_eq!(block.time_utc(), DateTime::UNIX_EPOCH);
+    }
+
+    #[test]
+    fn transaction_times_stay_optional() {
+        let mut tx = transaction(&"aa".repeat(32));
+        assert_eq!(tx.time_utc(), None);
+        tx.time = Some(1_454_124_731);
+        tx.blocktime = Some(0);
+        assert_eq!(tx.time_utc().unwrap().to_rfc3339(), "2016-01-30T03:32:11+00:00");
+        assert_eq!(tx.blocktime_utc(), Some(DateTime::UNIX_EPOCH));
+    }
+}
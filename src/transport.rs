@@ -0,0 +1,118 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+
+use failure::Error;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransportErrorKind {
+    ConnectionRefused,
+    Timeout,
+    Dns,
+    Tls,
+    Other,
+}
+
+/// The request never got a JSON-RPC reply, e.g. because the node is
+/// restarting. Unlike `RpcError` these are usually worth retrying.
+#[derive(Clone, Debug)]
+pub struct TransportError {
+    pub kind: TransportErrorKind,
+    pub message: String,
+}
+
+impl TransportError {
+    /// Classifies an error raised by the HTTP layer. Returns `None` for
+    /// anything else, so it can also be applied to errors from the
+    /// generated methods.
+    pub fn classify(err: &Error) -> Option<TransportError> {
+        if let Some(e) = err.downcast_ref::<TransportError>() {
+            return Some(e.clone());
+        }
+        let e = err.downcast_ref::<reqwest::Error>()?;
+        let mut kind = if e.is_timeout() { Some(TransportErrorKind::Timeout) } else { None };
+        let mut source = e.get_ref().map(|e| e as &(dyn StdError + 'static));
+        let mut saw_io = false;
+        while let (None, Some(e)) = (kind, source) {
+            if e.downcast_ref::<native_tls::Error>().is_some() {
+                kind = Some(TransportErrorKind::Tls);
+            }
+            source = match e.downcast_ref::<io::Error>() {
+                Some(io) => {
+                    saw_io = true;
+                    kind = kind.or(match io.kind() {
+                        io::ErrorKind::ConnectionRefused => Some(TransportErrorKind::ConnectionRefused),
+                        io::ErrorKind::TimedOut => Some(TransportErrorKind::Timeout),
+                        _ if io.to_string().contains("failed to lookup address") => Some(TransportErrorKind::Dns),
+                        _ => None,
+                    });
+                    io.get_ref().map(|e| e as &(dyn StdError + 'static))
+                }
+                None => e.source(),
+            };
+        }
+        let kind = match kind {
+            Some(kind) => kind,
+            None if saw_io || e.is_http() => TransportErrorKind::Other,
+            None => return None,
+        };
+        Some(TransportError {
+            kind,
+            message: e.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for TransportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "transport error ({:?}): {}", self.kind, self.message)
+    }
+}
+
+impl failure::Fail for TransportError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{MockChain, MockNode};
+    use crate::{BitcoinRpcClient, BitcoinRpcClientBuilder};
+    use std::net::TcpListener;
+    use std::time::Duration;
+
+    fn kind(err: &Error) -> Option<TransportErrorKind> {
+        TransportError::classify(err).map(|e| e.kind)
+    }
+
+    #[test]
+    fn refused_connection() {
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let client = BitcoinRpcClient::new(format!("http://127.0.0.1:{}", port), None, None, 0, 0, 0);
+        let err = client.getblockcount().unwrap_err();
+        assert_eq!(kind(&err), Some(TransportErrorKind::ConnectionRefused));
+        let err = client.getsupplyinfo(None).unwrap_err();
+        assert_eq!(err.downcast_ref::<TransportError>().map(|e| e.kind), Some(TransportErrorKind::ConnectionRefused));
+    }
+
+    #[test]
+    fn timeout() {
+        let mut chain = MockChain::new();
+        chain.push_block("00", vec![]);
+        let node = MockNode::start(chain).unwrap();
+        node.set_delay(Duration::from_millis(500));
+        let client = BitcoinRpcClientBuilder::new(node.url())
+            .timeout(Some(Duration::from_millis(50)))
+            .build()
+            .unwrap();
+        let err = client.getblockcount().unwrap_err();
+        assert_eq!(kind(&err), Some(TransportErrorKind::Timeout));
+    }
+
+    #[test]
+    fn rpc_errors_are_not_transport_errors() {
+        let node = MockNode::start(MockChain::new()).unwrap();
+        let err = node.client().getsupplyinfo(None).unwrap_err();
+        assert!(err.downcast_ref::<crate::RpcError>().is_some());
+        assert_eq!(kind(&err), None);
+        assert_eq!(kind(&format_err!("something else")), None);
+    }
+}